@@ -19,13 +19,20 @@ pub mod socket;
 pub mod utils;
 
 use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
 use nix::sys::epoll::EpollFlags;
 use nix::sys::signal::{Signal, SIGHUP};
 use nix::unistd::{daemon, getpid, getppid};
+use std::collections::HashMap;
 use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
 use std::os::unix::net::UnixStream;
-use std::process;
+use std::os::unix::process::CommandExt;
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use super::common::MSG_ENCLAVE_CONFIRM;
 use super::common::{enclave_proc_command_send_single, notify_error};
@@ -36,9 +43,263 @@ use crate::common::signal_handler::SignalHandler;
 
 use commands::{describe_enclaves, run_enclaves, terminate_enclaves};
 use connection::Connection;
-use connection_listener::ConnectionListener;
+use connection_listener::{ConnectionListener, EventLoopHandle};
 use resource_manager::EnclaveManager;
 
+/// Environment flag set on the re-exec'd image, signalling that it must resume from the
+/// inherited listening socket and enclave state rather than daemonizing from scratch.
+const REEXEC_ENV_FLAG: &str = "NITRO_CLI_ENCLAVE_PROC_REEXEC";
+/// Environment variable carrying the serialized `EnclaveManager` state blob across the exec boundary.
+const REEXEC_ENV_STATE: &str = "NITRO_CLI_ENCLAVE_PROC_STATE";
+/// Environment variable carrying the raw fd number of the inherited listening socket.
+const REEXEC_ENV_LISTENER_FD: &str = "NITRO_CLI_ENCLAVE_PROC_LISTENER_FD";
+
+/// Monotonic source of identifiers for in-flight cancellable operations.
+static NEXT_OPERATION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The status reported to the CLI when an operation is interrupted via `Cancel`.
+const STATUS_CANCELLED: i32 = libc::ECANCELED;
+
+/// A token allowing an in-flight enclave termination to be aborted.
+///
+/// Only terminations (operator- or watchdog-initiated) register a token; `Describe` runs to
+/// completion on the loop thread and cannot be cancelled.
+///
+/// The shared flag is polled by the worker at each blocking step; the event-loop handle lets
+/// `cancel` nudge the loop through its `mio::Waker` so the cancellation is noticed promptly
+/// rather than only on the next poll.
+#[derive(Clone)]
+struct CancellationToken {
+    /// The identifier of the operation this token guards.
+    operation_id: u64,
+    /// The flag the worker polls between blocking steps.
+    cancelled: Arc<AtomicBool>,
+    /// A handle used to wake the event loop when the operation is cancelled.
+    handle: EventLoopHandle,
+}
+
+impl CancellationToken {
+    /// Return whether the guarded operation has been cancelled.
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Flip the token and wake the event loop so the cancellation is observed.
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        // Nudge the loop out of its poll wait; registration/reply servicing happens on the
+        // loop thread, so a bare wake is all that is needed here.
+        self.handle.wake();
+    }
+}
+
+/// Registry of in-flight cancellable operations, used by the event loop; worker threads hold only
+/// the token of their own operation.
+#[derive(Clone, Default)]
+struct CancellationRegistry {
+    tokens: Arc<Mutex<HashMap<u64, CancellationToken>>>,
+}
+
+impl CancellationRegistry {
+    /// Register a new cancellable operation, returning its token. The supplied event-loop handle
+    /// is stored on the token so that cancelling the operation can wake the loop.
+    fn register(&self, handle: &EventLoopHandle) -> NitroCliResult<CancellationToken> {
+        let operation_id = NEXT_OPERATION_ID.fetch_add(1, Ordering::SeqCst);
+
+        let token = CancellationToken {
+            operation_id,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            handle: handle.clone(),
+        };
+
+        self.tokens
+            .lock()
+            .map_err(|_| "Cancellation registry lock poisoned".to_string())?
+            .insert(operation_id, token.clone());
+        Ok(token)
+    }
+
+    /// Flip the token for the given operation, returning whether it was still registered.
+    fn cancel(&self, operation_id: u64) -> NitroCliResult<bool> {
+        let tokens = self
+            .tokens
+            .lock()
+            .map_err(|_| "Cancellation registry lock poisoned".to_string())?;
+        Ok(match tokens.get(&operation_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        })
+    }
+
+    /// Remove a completed operation from the registry.
+    fn deregister(&self, operation_id: u64) {
+        if let Ok(mut tokens) = self.tokens.lock() {
+            tokens.remove(&operation_id);
+        }
+    }
+}
+
+/// What caused an enclave termination, letting subscribers and `Describe` callers tell an
+/// operator-initiated teardown apart from a watchdog-initiated one.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+enum TerminationReason {
+    /// Termination was requested by an operator (a `Terminate` command).
+    OperatorRequested,
+    /// Termination was initiated by the liveness watchdog after the enclave went silent.
+    WatchdogTimeout,
+}
+
+/// A lifecycle event pushed to `Subscribe` clients as soon as it is observed.
+///
+/// These mirror the transitions this module already computes while driving the event loop; the
+/// tag is serialized and written to every open subscriber connection.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum EnclaveLifecycleEvent {
+    /// The enclave has started running.
+    Running,
+    /// The enclave hung up; carries its exit code when one could be retrieved.
+    HangUp {
+        /// The enclave exit code, if known.
+        exit_code: Option<i32>,
+    },
+    /// An unexpected, non-fatal enclave epoll event was observed.
+    UnexpectedEvent,
+    /// Enclave termination has started.
+    TerminationStarted {
+        /// Why the termination was initiated.
+        reason: TerminationReason,
+    },
+    /// Enclave termination has completed.
+    TerminationCompleted,
+}
+
+/// A point-in-time snapshot of an enclave's resource consumption, reported by `GetStats` and the
+/// streaming `StreamStats` variant.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct EnclaveStats {
+    /// The full enclave identifier.
+    enclave_id: String,
+    /// The host CPU ids backing the enclave's vCPUs, indexed by vCPU number.
+    cpu_ids: Vec<u32>,
+    /// The size of the enclave's memory region, in MiB.
+    memory_mib: u64,
+    /// The time elapsed since the enclave was run, in seconds.
+    uptime_secs: u64,
+    /// The number of events observed on the enclave descriptor so far.
+    enclave_event_count: u64,
+}
+
+/// A client receiving a periodic stream of `EnclaveStats` frames over an open connection.
+struct StatsSubscriber {
+    /// The connection the stats frames are written to.
+    connection: Connection,
+    /// How often a frame is emitted.
+    interval: Duration,
+    /// The instant at which the next frame is due.
+    deadline: Instant,
+}
+
+impl StatsSubscriber {
+    /// The time remaining until the next frame is due, saturating at zero.
+    fn time_until_deadline(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+}
+
+/// Assemble a resource-usage snapshot from the enclave manager's tracked allocation.
+fn build_enclave_stats(enclave_manager: &EnclaveManager) -> NitroCliResult<EnclaveStats> {
+    let cpu_ids = enclave_manager
+        .get_cpu_ids()
+        .map_err(|e| format!("Failed to get enclave CPU allocation: {:?}", e))?;
+    let memory_mib = enclave_manager
+        .get_memory_mib()
+        .map_err(|e| format!("Failed to get enclave memory allocation: {:?}", e))?;
+
+    Ok(EnclaveStats {
+        enclave_id: enclave_manager.enclave_id.clone(),
+        cpu_ids,
+        memory_mib,
+        uptime_secs: enclave_manager.get_uptime().as_secs(),
+        enclave_event_count: enclave_manager.get_event_count(),
+    })
+}
+
+/// Emit a stats frame to every streaming subscriber whose interval has elapsed, dropping any whose
+/// write fails (e.g. EPIPE once the client has gone away).
+fn emit_due_stats(subscribers: &mut Vec<StatsSubscriber>, enclave_manager: &EnclaveManager) {
+    let now = Instant::now();
+    let mut retained = Vec::with_capacity(subscribers.len());
+
+    for mut subscriber in subscribers.drain(..) {
+        if now >= subscriber.deadline {
+            subscriber.deadline = now + subscriber.interval;
+            match build_enclave_stats(enclave_manager) {
+                Ok(frame) => {
+                    if let Err(e) = subscriber.connection.write(&frame) {
+                        info!("Dropping stats subscriber: {:?}", e);
+                        continue;
+                    }
+                }
+                Err(e) => warn!("Failed to build enclave stats: {:?}", e),
+            }
+        }
+        retained.push(subscriber);
+    }
+
+    *subscribers = retained;
+}
+
+/// A liveness watchdog that terminates an enclave that stops producing events.
+///
+/// The deadline is reset whenever enclave activity is observed or a health probe succeeds; if it
+/// elapses, the watchdog synthesizes the same teardown path as a `Terminate` command.
+struct Watchdog {
+    /// The grace period allowed between signs of life.
+    interval: Duration,
+    /// The instant at which the enclave is considered unresponsive.
+    deadline: Instant,
+}
+
+impl Watchdog {
+    /// Create a watchdog armed `interval` into the future.
+    fn new(interval: Duration) -> Self {
+        Watchdog {
+            interval,
+            deadline: Instant::now() + interval,
+        }
+    }
+
+    /// Push the deadline back out by a full interval.
+    fn reset(&mut self) {
+        self.deadline = Instant::now() + self.interval;
+    }
+
+    /// The time remaining until the deadline, saturating at zero.
+    fn time_until_deadline(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+
+    /// Whether the deadline has elapsed.
+    fn expired(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+}
+
+/// Write a lifecycle event to every subscriber, dropping any whose write fails (e.g. EPIPE once
+/// the client has gone away).
+fn broadcast_event(subscribers: &mut Vec<Connection>, event: &EnclaveLifecycleEvent) {
+    subscribers.retain(|subscriber| match subscriber.write(event) {
+        Ok(()) => true,
+        Err(e) => {
+            info!("Dropping enclave event subscriber: {:?}", e);
+            false
+        }
+    });
+}
+
 /// The type of enclave event that has been handled.
 enum HandledEnclaveEvent {
     /// A hang-up event.
@@ -73,19 +334,36 @@ fn notify_error_with_conn(err_msg: &str, conn: &Connection) {
 }
 
 /// Perform enclave termination.
+///
+/// The supplied cancellation `token` is polled by `terminate_enclaves` at each blocking step
+/// (enclave ioctl, slot free, resource release); if the operation is cancelled the enclave is
+/// left running and a distinct cancelled status is forwarded to the CLI. A watchdog-initiated
+/// termination has no originating CLI connection, so status reporting is skipped in that case.
 fn run_terminate(
-    connection: Connection,
+    connection: Option<Connection>,
     mut thread_stream: UnixStream,
     mut enclave_manager: EnclaveManager,
+    token: CancellationToken,
 ) {
-    terminate_enclaves(&mut enclave_manager, Some(&connection)).unwrap_or_else(|e| {
-        notify_error_with_conn(
-            &format!("Failed to terminate enclave: {:?}", e),
-            &connection,
-        );
-    });
+    terminate_enclaves(&mut enclave_manager, connection.as_ref(), Some(&token)).unwrap_or_else(
+        |e| match connection.as_ref() {
+            Some(conn) => {
+                notify_error_with_conn(&format!("Failed to terminate enclave: {:?}", e), conn)
+            }
+            None => notify_error(&format!("Failed to terminate enclave: {:?}", e)),
+        },
+    );
+
+    if token.is_cancelled() {
+        warn!("Enclave termination was cancelled; the enclave keeps running.");
+        if let Some(conn) = connection.as_ref() {
+            conn.write_status(STATUS_CANCELLED)
+                .ok_or_exit("Failed to send cancelled status.");
+        }
+    }
 
-    // Notify the main thread that enclave termination has completed.
+    // Notify the main thread that the termination thread has finished. The main thread inspects
+    // the token to distinguish a completed teardown from a cancelled one.
     send_command_and_close(
         EnclaveProcessCommandType::TerminateComplete,
         &mut thread_stream,
@@ -94,51 +372,194 @@ fn run_terminate(
 
 /// Start enclave termination.
 fn notify_terminate(
-    connection: Connection,
-    conn_listener: &ConnectionListener,
+    connection: Option<Connection>,
+    handle: &EventLoopHandle,
     enclave_manager: EnclaveManager,
+    token: CancellationToken,
 ) -> NitroCliResult<JoinHandle<()>> {
     let (local_stream, thread_stream) =
         UnixStream::pair().map_err(|e| format!("Failed to create stream pair: {:?}", e))?;
 
-    conn_listener
-        .add_stream_to_epoll(local_stream)
-        .map_err(|e| format!("Failed to add stream to epoll: {:?}", e))?;
+    // Hand the read half to the loop through the handle's queue; the registration is applied on
+    // the loop thread, so the termination worker can deliver its completion without racing the
+    // poll fd.
+    handle
+        .add_connection(local_stream)
+        .map_err(|e| format!("Failed to register termination channel: {:?}", e))?;
     Ok(thread::spawn(move || {
-        run_terminate(connection, thread_stream, enclave_manager)
+        run_terminate(connection, thread_stream, enclave_manager, token)
     }))
 }
 
+/// Begin an enclave termination for the given reason, wiring up cancellation and announcing the
+/// transition to subscribers. This is the single path shared by the `Terminate` command and the
+/// liveness watchdog; the latter passes `None` for `connection` as there is no CLI to reply to.
+#[allow(clippy::too_many_arguments)]
+fn start_termination(
+    reason: TerminationReason,
+    connection: Option<Connection>,
+    handle: &EventLoopHandle,
+    enclave_manager: &mut EnclaveManager,
+    cancel_registry: &CancellationRegistry,
+    terminate_thread: &mut Option<JoinHandle<()>>,
+    terminate_token: &mut Option<CancellationToken>,
+    subscribers: &mut Vec<Connection>,
+) -> NitroCliResult<()> {
+    // Register a cancellation token so a subsequent `Cancel` command can interrupt the teardown
+    // if the enclave hangs. The token is handed to the termination worker and kept here so that
+    // `Cancel` can flip it and `TerminateComplete` can clean it up.
+    let token = cancel_registry.register(handle)?;
+    *terminate_token = Some(token.clone());
+
+    // Hand the operation id back to the CLI so it can later target this termination with a
+    // `Cancel` command. This must happen before the termination worker below is spawned: that
+    // worker holds its own clone of `connection` and writes teardown output to it concurrently,
+    // so writing the id any later risks interleaving it with the worker's bytes.
+    if let Some(conn) = connection.as_ref() {
+        conn.write_u64(token.operation_id)
+            .map_err(|e| format!("Failed to send operation id: {}", e))?;
+    }
+
+    // Record the reason so `Describe` can report it, and announce the transition.
+    enclave_manager.set_termination_reason(reason);
+    broadcast_event(subscribers, &EnclaveLifecycleEvent::TerminationStarted { reason });
+
+    *terminate_thread = Some(notify_terminate(
+        connection,
+        handle,
+        enclave_manager.clone(),
+        token,
+    )?);
+    Ok(())
+}
+
 /// Launch the POSIX signal handler on a dedicated thread and ensure its events are accessible.
-fn enclave_proc_configure_signal_handler(conn_listener: &ConnectionListener) -> NitroCliResult<()> {
+fn enclave_proc_configure_signal_handler(handle: &EventLoopHandle) -> NitroCliResult<()> {
     let mut signal_handler = SignalHandler::new_with_defaults().mask_all();
     let (local_stream, thread_stream) =
         UnixStream::pair().ok_or_exit("Failed to create stream pair.");
 
-    conn_listener
-        .add_stream_to_epoll(local_stream)
-        .map_err(|e| format!("Failed to add stream to epoll: {:?}", e))?;
+    handle
+        .add_connection(local_stream)
+        .map_err(|e| format!("Failed to register signal channel: {:?}", e))?;
     signal_handler.start_handler(thread_stream.into_raw_fd(), enclave_proc_handle_signals);
 
     Ok(())
 }
 
-/// The default POSIX signal handling function, which notifies the enclave process to shut down gracefully.
+/// The default POSIX signal handling function.
+///
+/// Most signals notify the enclave process to shut down gracefully. SIGHUP is special-cased
+/// to request a re-exec of the enclave-process image, allowing zero-downtime upgrades of the
+/// CLI binary while the managed enclave keeps running.
 fn enclave_proc_handle_signals(comm_fd: RawFd, signal: Signal) -> bool {
     let mut stream = unsafe { UnixStream::from_raw_fd(comm_fd) };
 
-    warn!(
-        "Received signal {:?}. The enclave process will now close.",
-        signal
-    );
-    send_command_and_close(
-        EnclaveProcessCommandType::ConnectionListenerStop,
-        &mut stream,
-    );
+    match signal {
+        SIGHUP => {
+            warn!("Received SIGHUP. The enclave process will re-exec its image.");
+            send_command_and_close(EnclaveProcessCommandType::ReexecRequested, &mut stream);
+        }
+        _ => {
+            warn!(
+                "Received signal {:?}. The enclave process will now close.",
+                signal
+            );
+            send_command_and_close(
+                EnclaveProcessCommandType::ConnectionListenerStop,
+                &mut stream,
+            );
+        }
+    }
 
     true
 }
 
+/// Clear or set the close-on-exec flag on a descriptor so that it can (or cannot) survive an exec().
+fn set_cloexec(fd: RawFd, cloexec: bool) -> NitroCliResult<()> {
+    let mut flags = FdFlag::from_bits_truncate(
+        fcntl(fd, FcntlArg::F_GETFD).map_err(|e| format!("Failed to get fd flags: {:?}", e))?,
+    );
+    flags.set(FdFlag::FD_CLOEXEC, cloexec);
+    fcntl(fd, FcntlArg::F_SETFD(flags))
+        .map_err(|e| format!("Failed to set fd flags: {:?}", e))?;
+    Ok(())
+}
+
+/// Re-execute the enclave-process image in place, handing off the listening socket and the
+/// enclave descriptor to the new image so the running enclave is never torn down.
+///
+/// The listening socket stops accepting new connections before the exec, the inherited fds have
+/// their close-on-exec flag cleared and the `EnclaveManager` state is serialized into the
+/// environment. On success this function never returns; on failure it returns the error of the
+/// step that failed (pausing the listener, preparing the descriptors, or the exec itself).
+fn reexec_enclave_process(
+    conn_listener: &ConnectionListener,
+    enclave_manager: &EnclaveManager,
+) -> NitroCliResult<()> {
+    // Stop accepting new connections, but keep the listening socket open so that the
+    // re-exec'd image can inherit it.
+    conn_listener
+        .pause()
+        .map_err(|e| format!("Failed to pause connection listener: {:?}", e))?;
+
+    // The listening socket and the enclave descriptor must survive the exec().
+    let listener_fd = conn_listener
+        .as_raw_listener_fd()
+        .map_err(|e| format!("Failed to get listener descriptor: {:?}", e))?;
+    let enc_fd = enclave_manager
+        .get_enclave_descriptor()
+        .map_err(|e| format!("Failed to get enclave descriptor: {:?}", e))?;
+    set_cloexec(listener_fd, false)?;
+    set_cloexec(enc_fd, false)?;
+
+    // Serialize the manager state (enclave_id, CID, slot/cpu allocation, raw enclave fd) so the
+    // new image can rebuild it via `EnclaveManager::from_state_blob`.
+    let state_blob = enclave_manager.serialize_state();
+
+    let exe = std::fs::read_link("/proc/self/exe")
+        .map_err(|e| format!("Failed to resolve own executable path: {:?}", e))?;
+
+    warn!("Re-executing enclave process image {:?}.", exe);
+
+    // `exec` replaces the current image and only returns on failure.
+    let err = Command::new(exe)
+        .env(REEXEC_ENV_FLAG, "1")
+        .env(REEXEC_ENV_STATE, state_blob)
+        .env(REEXEC_ENV_LISTENER_FD, listener_fd.to_string())
+        .exec();
+
+    Err(format!("Failed to re-exec enclave process: {:?}", err))
+}
+
+/// Reconstruct the event-loop state from the fds and blob inherited across a SIGHUP re-exec.
+fn reconstruct_after_reexec() -> NitroCliResult<(ConnectionListener, EnclaveManager)> {
+    let listener_fd: RawFd = std::env::var(REEXEC_ENV_LISTENER_FD)
+        .map_err(|e| format!("Failed to read inherited listener fd: {:?}", e))?
+        .parse()
+        .map_err(|e| format!("Failed to parse inherited listener fd: {:?}", e))?;
+    let state_blob = std::env::var(REEXEC_ENV_STATE)
+        .map_err(|e| format!("Failed to read inherited state blob: {:?}", e))?;
+
+    // Rebuild the listening socket from the inherited descriptor and set the CLOEXEC flag we
+    // had to clear before the exec, restoring the default behaviour for future children.
+    set_cloexec(listener_fd, true)?;
+    let conn_listener = unsafe { ConnectionListener::from_raw_listener_fd(listener_fd) };
+
+    let enclave_manager = EnclaveManager::from_state_blob(&state_blob)
+        .map_err(|e| format!("Failed to rebuild enclave manager: {:?}", e))?;
+
+    // Re-arm epoll for the inherited enclave descriptor, restoring FD_CLOEXEC on it the same way
+    // we did for the listener fd above, so it doesn't leak into a later fork/exec.
+    let enc_fd = enclave_manager
+        .get_enclave_descriptor()
+        .map_err(|e| format!("Failed to get enclave descriptor: {:?}", e))?;
+    set_cloexec(enc_fd, true)?;
+    conn_listener.register_enclave_descriptor(enc_fd);
+
+    Ok((conn_listener, enclave_manager))
+}
+
 /// Handle an event coming from an enclave.
 fn try_handle_enclave_event(connection: &Connection) -> NitroCliResult<HandledEnclaveEvent> {
     // Check if this is an enclave connection.
@@ -168,13 +589,20 @@ fn try_handle_enclave_event(connection: &Connection) -> NitroCliResult<HandledEn
 }
 
 /// Handle a single command, returning whenever an error occurs.
+#[allow(clippy::too_many_arguments)]
 fn handle_command(
     cmd: EnclaveProcessCommandType,
     logger: &EnclaveProcLogWriter,
     connection: &Connection,
     conn_listener: &mut ConnectionListener,
+    handle: &EventLoopHandle,
     enclave_manager: &mut EnclaveManager,
     terminate_thread: &mut Option<std::thread::JoinHandle<()>>,
+    cancel_registry: &CancellationRegistry,
+    terminate_token: &mut Option<CancellationToken>,
+    subscribers: &mut Vec<Connection>,
+    stats_subscribers: &mut Vec<StatsSubscriber>,
+    watchdog: &mut Option<Watchdog>,
 ) -> NitroCliResult<(i32, bool)> {
     Ok(match cmd {
         EnclaveProcessCommandType::Run => {
@@ -201,22 +629,83 @@ fn handle_command(
                     .get_enclave_descriptor()
                     .map_err(|e| format!("Failed to get enclave descriptor: {:?}", e))?;
                 conn_listener.register_enclave_descriptor(enc_fd);
+
+                // Arm the liveness watchdog if the enclave opted in to one.
+                if let Some(interval_ms) = run_args.watchdog_interval_ms {
+                    info!("Arming liveness watchdog with a {} ms interval.", interval_ms);
+                    *watchdog = Some(Watchdog::new(Duration::from_millis(interval_ms)));
+                }
+
+                // Note: there is no point broadcasting `Running` here — `Run` is necessarily the
+                // first command handled, so `subscribers` is always empty at this point. A
+                // `Subscribe`r instead gets `Running` replayed below as soon as it registers.
                 (0, false)
             }
         }
 
+        EnclaveProcessCommandType::Subscribe => {
+            // Keep the connection open and hand a clone to the fan-out; lifecycle events are
+            // pushed to it until its write fails.
+            info!("Registered a new enclave lifecycle subscriber.");
+            if !enclave_manager.enclave_id.is_empty() {
+                // The enclave was already running before this subscriber registered, so it would
+                // otherwise never see the `Running` transition; replay it directly.
+                let _ = connection.write(&EnclaveLifecycleEvent::Running);
+            }
+            subscribers.push(connection.clone());
+            (0, false)
+        }
+
         EnclaveProcessCommandType::Terminate => {
-            *terminate_thread = Some(notify_terminate(
-                connection.clone(),
-                conn_listener,
-                enclave_manager.clone(),
-            )?);
+            // `start_termination` writes the operation id back to `connection` itself, before it
+            // spawns the termination worker that also writes to a clone of it.
+            start_termination(
+                TerminationReason::OperatorRequested,
+                Some(connection.clone()),
+                handle,
+                enclave_manager,
+                cancel_registry,
+                terminate_thread,
+                terminate_token,
+                subscribers,
+            )?;
             (0, false)
         }
 
+        EnclaveProcessCommandType::Cancel => {
+            // The CLI carries the identifier of the operation it wants to interrupt.
+            let operation_id = connection
+                .read_u64()
+                .map_err(|e| format!("Failed to read cancel target: {}", e))?;
+            let status = if cancel_registry.cancel(operation_id)? {
+                info!("Requested cancellation of operation {}.", operation_id);
+                0
+            } else {
+                // There is no such in-flight operation (it may have already completed).
+                libc::ESRCH
+            };
+            (status, false)
+        }
+
         EnclaveProcessCommandType::TerminateComplete => {
-            info!("Enclave has completed termination.");
-            (0, true)
+            // Retire the cancellation token for the operation that just finished, noting whether
+            // it was cancelled: a cancelled termination leaves the enclave running, so the process
+            // must keep serving rather than exit the event loop.
+            let cancelled = match terminate_token.take() {
+                Some(token) => {
+                    cancel_registry.deregister(token.operation_id);
+                    token.is_cancelled()
+                }
+                None => false,
+            };
+
+            if cancelled {
+                info!("Enclave termination was cancelled; the enclave keeps running.");
+            } else {
+                info!("Enclave has completed termination.");
+                broadcast_event(subscribers, &EnclaveLifecycleEvent::TerminationCompleted);
+            }
+            (0, !cancelled)
         }
 
         EnclaveProcessCommandType::GetEnclaveCID => {
@@ -239,6 +728,53 @@ fn handle_command(
             (0, false)
         }
 
+        EnclaveProcessCommandType::GetStats => {
+            connection
+                .write_u64(MSG_ENCLAVE_CONFIRM)
+                .map_err(|e| format!("Failed to write confirmation: {}", e))?;
+
+            let stats = build_enclave_stats(enclave_manager)?;
+            connection
+                .write(&stats)
+                .map_err(|e| format!("Failed to send enclave stats: {}", e))?;
+            (0, false)
+        }
+
+        EnclaveProcessCommandType::StreamStats => {
+            // The CLI carries the reporting interval, in milliseconds.
+            let interval_ms = connection
+                .read_u64()
+                .map_err(|e| format!("Failed to read stats interval: {}", e))?;
+            let interval = Duration::from_millis(interval_ms.max(1));
+            info!(
+                "Registered a streaming stats subscriber at a {} ms interval.",
+                interval_ms
+            );
+            stats_subscribers.push(StatsSubscriber {
+                connection: connection.clone(),
+                interval,
+                deadline: Instant::now() + interval,
+            });
+            (0, false)
+        }
+
+        EnclaveProcessCommandType::ReexecRequested => {
+            // `reexec_enclave_process` only returns on failure; a successful exec replaces the
+            // image and never reaches here. It already paused the connection listener before
+            // attempting the exec, so on failure we must resume it and keep serving rather than
+            // tear the daemon down and orphan the still-running enclave.
+            if let Err(e) = reexec_enclave_process(conn_listener, enclave_manager) {
+                warn!(
+                    "Failed to re-exec enclave process: {}; resuming normal operation.",
+                    e
+                );
+                conn_listener
+                    .resume()
+                    .map_err(|e| format!("Failed to resume connection listener: {:?}", e))?;
+            }
+            (0, false)
+        }
+
         EnclaveProcessCommandType::ConnectionListenerStop => (0, true),
 
         EnclaveProcessCommandType::NotPermitted => (libc::EACCES, false),
@@ -249,34 +785,112 @@ fn handle_command(
 fn process_event_loop(
     comm_stream: UnixStream,
     logger: &EnclaveProcLogWriter,
+    resumed: Option<(ConnectionListener, EnclaveManager)>,
 ) -> NitroCliResult<()> {
-    let mut conn_listener = ConnectionListener::new();
-    let mut enclave_manager = EnclaveManager::default();
+    let resuming = resumed.is_some();
+    let (mut conn_listener, mut enclave_manager) =
+        resumed.unwrap_or_else(|| (ConnectionListener::new(), EnclaveManager::default()));
     let mut terminate_thread: Option<std::thread::JoinHandle<()>> = None;
+    let cancel_registry = CancellationRegistry::default();
+    let mut terminate_token: Option<CancellationToken> = None;
+    let mut subscribers: Vec<Connection> = Vec::new();
+    let mut stats_subscribers: Vec<StatsSubscriber> = Vec::new();
+    let mut watchdog: Option<Watchdog> = None;
     let mut done = false;
     let mut ret_value = Ok(());
 
+    // A clonable, `Send` handle through which other threads post `add_connection`, `wake` and
+    // `shutdown` messages; these are serviced only inside the loop thread, making cross-thread
+    // connection registration race-free.
+    let handle = conn_listener.handle();
+
     // Start the signal handler before spawning any other threads. This is done since the
     // handler will mask all relevant signals from the current thread and this setting will
     // be automatically inherited by all threads spawned from this point on; we want this
     // because only the dedicated thread spawned by the handler should listen for signals.
-    enclave_proc_configure_signal_handler(&conn_listener)
+    enclave_proc_configure_signal_handler(&handle)
         .map_err(|e| format!("Failed to configure signal handler: {:?}", e))?;
 
-    // Add the CLI communication channel to epoll.
-    conn_listener
-        .handle_new_connection(comm_stream)
-        .map_err(|e| format!("Failed to register new connection with epoll: {:?}", e))?;
+    if resuming {
+        // We resumed from a SIGHUP re-exec: the listening socket is already bound and the enclave
+        // descriptor is already registered, so resume accepting connections on it.
+        conn_listener
+            .resume()
+            .map_err(|e| format!("Failed to resume connection listener: {:?}", e))?;
+    } else {
+        // Add the CLI communication channel to epoll.
+        conn_listener
+            .handle_new_connection(comm_stream)
+            .map_err(|e| format!("Failed to register new connection with epoll: {:?}", e))?;
+    }
 
     while !done {
+        // Bound the poll wait by the nearest deadline among the watchdog (if armed) and any stats
+        // streams, so that both a silent enclave and due stats frames wake the loop. Without any
+        // deadline the loop blocks until the next connection.
+        let mut timeout = watchdog.as_ref().map(Watchdog::time_until_deadline);
+        for subscriber in &stats_subscribers {
+            let due = subscriber.time_until_deadline();
+            timeout = Some(timeout.map_or(due, |t| t.min(due)));
+        }
+
         // We can get connections to CLI instances, to the enclave or to ourselves.
-        let connection =
-            conn_listener.get_next_connection(enclave_manager.get_enclave_descriptor().ok());
+        let connection = match conn_listener
+            .get_next_connection(enclave_manager.get_enclave_descriptor().ok(), timeout)
+        {
+            Some(connection) => connection,
+            None => {
+                // The poll timed out. Push a frame to any streaming stats subscriber that is due.
+                emit_due_stats(&mut stats_subscribers, &enclave_manager);
+
+                // If the watchdog deadline elapsed with no enclave activity, give the enclave one
+                // last chance via a health probe before tearing it down.
+                if watchdog.as_ref().map_or(false, Watchdog::expired) {
+                    if enclave_manager.health_probe().unwrap_or(false) {
+                        watchdog.as_mut().unwrap().reset();
+                    } else {
+                        let elapsed = watchdog.as_ref().unwrap().interval;
+                        warn!(
+                            "Watchdog timeout: no enclave activity within {:?}; terminating enclave.",
+                            elapsed
+                        );
+                        start_termination(
+                            TerminationReason::WatchdogTimeout,
+                            None,
+                            &handle,
+                            &mut enclave_manager,
+                            &cancel_registry,
+                            &mut terminate_thread,
+                            &mut terminate_token,
+                            &mut subscribers,
+                        )?;
+                        // Disarm the watchdog while the teardown is in flight.
+                        watchdog = None;
+                    }
+                }
+                continue;
+            }
+        };
 
-        // If this is an enclave event, handle it.
+        // If this is an enclave event, handle it. Only genuine enclave activity rearms the
+        // watchdog here; CLI connections (commands, subscribers, stats streams) must not keep it
+        // perpetually rearmed, or a hung enclave would never be reaped while a client is active.
         match try_handle_enclave_event(&connection) {
-            Ok(HandledEnclaveEvent::HangUp) => break,
-            Ok(HandledEnclaveEvent::Unexpected) => continue,
+            Ok(HandledEnclaveEvent::HangUp) => {
+                if let Some(watchdog) = watchdog.as_mut() {
+                    watchdog.reset();
+                }
+                let exit_code = enclave_manager.get_exit_code().ok();
+                broadcast_event(&mut subscribers, &EnclaveLifecycleEvent::HangUp { exit_code });
+                break;
+            }
+            Ok(HandledEnclaveEvent::Unexpected) => {
+                if let Some(watchdog) = watchdog.as_mut() {
+                    watchdog.reset();
+                }
+                broadcast_event(&mut subscribers, &EnclaveLifecycleEvent::UnexpectedEvent);
+                continue;
+            }
             Ok(HandledEnclaveEvent::None) => (),
             Err(err_str) => {
                 ret_value = Err(format!("Failed to handle enclave event: {:?}", err_str));
@@ -300,8 +914,14 @@ fn process_event_loop(
             logger,
             &connection,
             &mut conn_listener,
+            &handle,
             &mut enclave_manager,
             &mut terminate_thread,
+            &cancel_registry,
+            &mut terminate_token,
+            &mut subscribers,
+            &mut stats_subscribers,
+            &mut watchdog,
         );
 
         // Obtain the status code and whether the event loop must be exited.
@@ -320,8 +940,11 @@ fn process_event_loop(
         // This is done to avoid race conditions where the enclave process has not yet removed the
         // socket and another CLI issues a command on that very-soon-to-be-removed socket.
         if done {
-            // Stop the connection listener.
+            // Stop the connection listener and shut down the mio event loop thread behind it.
             conn_listener.stop();
+            handle
+                .shutdown()
+                .map_err(|e| format!("Failed to shut down event loop: {:?}", e))?;
 
             // Wait for the termination thread, if any.
             if terminate_thread.is_some() {
@@ -337,7 +960,9 @@ fn process_event_loop(
         match cmd {
             EnclaveProcessCommandType::Run
             | EnclaveProcessCommandType::Terminate
-            | EnclaveProcessCommandType::Describe => connection
+            | EnclaveProcessCommandType::Cancel
+            | EnclaveProcessCommandType::Describe
+            | EnclaveProcessCommandType::GetStats => connection
                 .write_status(status_code)
                 .ok_or_exit("Failed to send status reply."),
             _ => (),
@@ -382,8 +1007,21 @@ fn create_enclave_process(logger: &EnclaveProcLogWriter) {
 /// * `comm_fd` - A descriptor used for initial communication with the parent Nitro CLI instance.
 /// * `logger` - The current log writer, whose ID gets updated when an enclave is launched.
 pub fn enclave_process_run(comm_stream: UnixStream, logger: &EnclaveProcLogWriter) {
-    create_enclave_process(logger);
-    let res = process_event_loop(comm_stream, logger);
+    let res = if std::env::var_os(REEXEC_ENV_FLAG).is_some() {
+        // We are the freshly exec'd image of a SIGHUP upgrade; resume from inherited state
+        // instead of daemonizing anew. The real enclave id is only known once the state blob
+        // has been reconstructed, so the logger keeps its prior id (inherited from the
+        // environment) until then rather than being reset to a placeholder.
+        info!("Resuming enclave process {} after re-exec.", process::id());
+        reconstruct_after_reexec().and_then(|state| {
+            logger.update_logger_id(&get_logger_id(&state.1.enclave_id));
+            process_event_loop(comm_stream, logger, Some(state))
+        })
+    } else {
+        create_enclave_process(logger);
+        process_event_loop(comm_stream, logger, None)
+    };
+
     if let Err(err_str) = res {
         notify_error(&err_str);
     }